@@ -0,0 +1,47 @@
+// common types shared between `Sender` and `Receiver`
+
+/// a stat that should be tracked and exported by a `Receiver`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Interest<T> {
+    /// track a running total for the channel
+    Count(T),
+    /// track a latency distribution for the channel
+    Percentile(T),
+    /// track the allan deviation for the channel
+    AllanDeviation(T),
+    /// track the most recently observed value for the channel
+    Gauge(T),
+    /// write a trace of all heatmap samples to the given file on exit
+    Trace(T, String),
+    /// write a waterfall rendering of the heatmap to the given file on exit
+    Waterfall(T, String),
+}
+
+/// a message sent from a `Sender` to a `Receiver` to change what is tracked
+#[derive(Clone, Debug)]
+pub enum ControlMessage<T> {
+    AddInterest(Interest<T>),
+    RemoveInterest(Interest<T>),
+}
+
+/// a percentile to export, pairing a display label with the fractional rank
+#[derive(Clone, Debug, PartialEq)]
+pub struct Percentile(pub String, pub f64);
+
+/// the default set of percentiles tracked for `Percentile` interests; labels
+/// are the fractional rank rendered as a string, matching the Prometheus
+/// convention of a `quantile="0.99"` label rather than a `p99`-style name
+pub fn default_percentiles() -> Vec<Percentile> {
+    vec![
+        Percentile("0.5".to_owned(), 0.5),
+        Percentile("0.9".to_owned(), 0.9),
+        Percentile("0.99".to_owned(), 0.99),
+        Percentile("0.999".to_owned(), 0.999),
+    ]
+}
+
+/// the default set of observation intervals (counted in samples) tracked for
+/// `AllanDeviation` interests
+pub fn default_taus() -> Vec<usize> {
+    vec![1, 2, 4, 8, 16, 32, 64, 128, 256]
+}
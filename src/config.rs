@@ -0,0 +1,104 @@
+// `Config` is a builder for a `Receiver`
+
+use crate::receiver::Receiver;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct Config<T> {
+    resource_type: PhantomData<T>,
+    pub(crate) capacity: usize,
+    pub(crate) batch_size: usize,
+    pub(crate) duration: usize,
+    pub(crate) windows: usize,
+    pub(crate) poll_delay: Option<Duration>,
+    pub(crate) service_mode: bool,
+    pub(crate) http_listen: Option<String>,
+    pub(crate) influx_target: Option<String>,
+    pub(crate) influx_flush: usize,
+}
+
+impl<T> Default for Config<T> {
+    fn default() -> Config<T> {
+        Config {
+            resource_type: PhantomData::<T>,
+            capacity: 1024,
+            batch_size: 64,
+            duration: 60,
+            windows: 60,
+            poll_delay: Some(Duration::from_millis(100)),
+            service_mode: false,
+            http_listen: None,
+            influx_target: None,
+            influx_flush: 1,
+        }
+    }
+}
+
+impl<T: Hash + Eq + Send + Display + Clone> Config<T> {
+    /// create a new `Config` with defaults
+    pub fn new() -> Config<T> {
+        Default::default()
+    }
+
+    /// build the `Receiver` from this `Config`
+    pub fn build(self) -> Receiver<T> {
+        Receiver::configured(self)
+    }
+
+    /// set the capacity of the data and control channels
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// set the number of samples batched per `Sender` flush
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// set the duration of a single window, in seconds
+    pub fn duration(mut self, duration: usize) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// set the number of windows collected before stats are saved
+    pub fn windows(mut self, windows: usize) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// set the maximum time to block waiting on the data/control channels
+    pub fn poll_delay(mut self, poll_delay: Option<Duration>) -> Self {
+        self.poll_delay = poll_delay;
+        self
+    }
+
+    /// run forever, restarting collection after each set of `windows` completes
+    pub fn service_mode(mut self, service_mode: bool) -> Self {
+        self.service_mode = service_mode;
+        self
+    }
+
+    /// listen for stat exposition requests (`/metrics`, `/vars`, ...) on this address
+    pub fn http_listen(mut self, addr: String) -> Self {
+        self.http_listen = Some(addr);
+        self
+    }
+
+    /// push window snapshots as InfluxDB line protocol to this URL
+    pub fn influx_target(mut self, target: String) -> Self {
+        self.influx_target = Some(target);
+        self
+    }
+
+    /// push to the influx target every `windows` windows, rather than every window
+    pub fn influx_flush(mut self, windows: usize) -> Self {
+        self.influx_flush = windows;
+        self
+    }
+}
@@ -0,0 +1,365 @@
+// data structures that back a `Receiver`'s stat aggregation
+
+use crate::histogram::AtomicHistogram;
+use fnv::FnvHashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+pub use crate::meters::Meters;
+
+/// a single timestamped observation handed from a `Sender` to a `Receiver`
+/// across the data channel
+#[derive(Clone)]
+pub struct Sample<T> {
+    metric: T,
+    start: u64,
+    stop: u64,
+    count: u64,
+}
+
+impl<T: Clone> Sample<T> {
+    pub fn new(metric: T, start: u64, stop: u64, count: u64) -> Sample<T> {
+        Sample {
+            metric,
+            start,
+            stop,
+            count,
+        }
+    }
+
+    pub fn metric(&self) -> T {
+        self.metric.clone()
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// lock-free running totals, keyed by an interned index so that the hot
+/// increment path (`Sender::record_counter`) never hashes `T` or takes a
+/// write lock
+pub struct Counters<T: Hash + Eq> {
+    index: Arc<RwLock<FnvHashMap<T, usize>>>,
+    slots: Arc<RwLock<Vec<Arc<AtomicU64>>>>,
+}
+
+impl<T: Hash + Eq> Counters<T> {
+    pub fn new() -> Counters<T> {
+        Counters {
+            index: Arc::new(RwLock::new(FnvHashMap::default())),
+            slots: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Counters<T> {
+    /// allocate the atomic slot backing `key`, returning its index; calling
+    /// this again for an already-registered key is a no-op that returns the
+    /// existing index
+    pub fn init(&self, key: T) -> usize {
+        if let Some(&id) = self.index.read().unwrap().get(&key) {
+            return id;
+        }
+        let mut index = self.index.write().unwrap();
+        if let Some(&id) = index.get(&key) {
+            return id;
+        }
+        let mut slots = self.slots.write().unwrap();
+        let id = slots.len();
+        slots.push(Arc::new(AtomicU64::new(0)));
+        index.insert(key, id);
+        id
+    }
+
+    /// de-register `key`; the underlying slot is left in place so that any
+    /// in-flight `record()` call racing this removal can't index out of
+    /// bounds, it just stops being reachable by key
+    pub fn remove(&self, key: T) {
+        self.index.write().unwrap().remove(&key);
+    }
+
+    /// snapshot the current total for `key`; a relaxed load, so it never
+    /// contends with fast-path writers
+    pub fn count(&self, key: T) -> u64 {
+        match self.index.read().unwrap().get(&key) {
+            Some(&id) => self.slots.read().unwrap()[id].load(Ordering::Relaxed),
+            None => 0,
+        }
+    }
+
+    /// atomic fast path: a plain `fetch_add` on the slot for `id`, with no
+    /// hashing and no write lock
+    pub fn record(&self, id: usize, count: u64) {
+        if let Some(slot) = self.slots.read().unwrap().get(id) {
+            slot.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T: Hash + Eq> Clone for Counters<T> {
+    fn clone(&self) -> Counters<T> {
+        Counters {
+            index: self.index.clone(),
+            slots: self.slots.clone(),
+        }
+    }
+}
+
+/// tracks the most recently observed value per channel; unlike `Counters`
+/// this isn't shared with `Sender`, gauge samples still arrive over the data
+/// channel since they carry no delta semantics worth a dedicated fast path.
+/// unlike histograms, gauges are never cleared at a window boundary, since
+/// they represent current state rather than an accumulation over the window
+pub struct Gauges<T: Hash + Eq> {
+    data: FnvHashMap<T, u64>,
+}
+
+impl<T: Hash + Eq> Gauges<T> {
+    pub fn new() -> Gauges<T> {
+        Gauges {
+            data: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Gauges<T> {
+    pub fn init(&mut self, key: T) {
+        self.data.entry(key).or_insert(0);
+    }
+
+    pub fn remove(&mut self, key: T) {
+        self.data.remove(&key);
+    }
+
+    /// record the most recently observed value; a no-op if `key` has no
+    /// registered `Gauge` interest, so unregistered channels can't grow this
+    /// map without bound
+    pub fn set(&mut self, key: T, value: u64) {
+        if let Some(slot) = self.data.get_mut(&key) {
+            *slot = value;
+        }
+    }
+
+    pub fn get(&self, key: T) -> Option<u64> {
+        self.data.get(&key).cloned()
+    }
+}
+
+// bits of linear resolution within each power-of-two bucket of a channel's
+// `AtomicHistogram`; 12 bits keeps relative error under the bucket width
+// small while bounding the number of blocks a channel ever allocates
+const HISTOGRAM_PRECISION: u32 = 12;
+
+/// per-channel latency distributions, backed by a lock-free `AtomicHistogram`
+/// so they can be fed directly from the data channel without contending with
+/// concurrent writers; cleared at each window boundary
+pub struct Histograms<T: Hash + Eq> {
+    data: FnvHashMap<T, AtomicHistogram>,
+}
+
+impl<T: Hash + Eq> Histograms<T> {
+    pub fn new() -> Histograms<T> {
+        Histograms {
+            data: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Histograms<T> {
+    pub fn init(&mut self, key: T) {
+        self.data
+            .entry(key)
+            .or_insert_with(|| AtomicHistogram::new(HISTOGRAM_PRECISION));
+    }
+
+    pub fn remove(&mut self, key: T) {
+        self.data.remove(&key);
+    }
+
+    pub fn increment(&mut self, key: T, value: u64) {
+        if let Some(histogram) = self.data.get(&key) {
+            histogram.increment(value);
+        }
+    }
+
+    pub fn percentile(&self, key: T, target: f64) -> Option<u64> {
+        self.data.get(&key).map(|histogram| histogram.percentile(target))
+    }
+
+    /// the observation count and approximate sum (see `AtomicHistogram::sum`)
+    /// recorded for `key` since the last `clear()`, for exposing a Prometheus
+    /// summary's `_count`/`_sum`
+    pub fn stats(&self, key: T) -> Option<(u64, u64)> {
+        self.data
+            .get(&key)
+            .map(|histogram| (histogram.count(), histogram.sum()))
+    }
+
+    pub fn clear(&mut self) {
+        for histogram in self.data.values() {
+            histogram.clear();
+        }
+    }
+}
+
+/// per-channel allan deviation, computed over every raw latency sample seen
+/// since the channel was registered; samples are only dropped by `remove()`
+pub struct Allans<T: Hash + Eq> {
+    data: FnvHashMap<T, Vec<f64>>,
+}
+
+impl<T: Hash + Eq> Allans<T> {
+    pub fn new() -> Allans<T> {
+        Allans {
+            data: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Allans<T> {
+    pub fn init(&mut self, key: T) {
+        self.data.entry(key).or_default();
+    }
+
+    pub fn remove(&mut self, key: T) {
+        self.data.remove(&key);
+    }
+
+    pub fn record(&mut self, key: T, value: f64) {
+        if let Some(values) = self.data.get_mut(&key) {
+            values.push(value);
+        }
+    }
+
+    /// Allan deviation at observation interval `tau` (in samples), or `None`
+    /// if fewer than two non-overlapping windows of `tau` samples exist yet
+    pub fn adev(&self, key: T, tau: usize) -> Option<f64> {
+        let values = self.data.get(&key)?;
+        if tau == 0 || values.len() < 2 * tau {
+            return None;
+        }
+        let averages: Vec<f64> = values
+            .chunks(tau)
+            .filter(|chunk| chunk.len() == tau)
+            .map(|chunk| chunk.iter().sum::<f64>() / tau as f64)
+            .collect();
+        if averages.len() < 2 {
+            return None;
+        }
+        let sum_sq: f64 = averages.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+        let n = (averages.len() - 1) as f64;
+        Some((sum_sq / (2.0 * n)).sqrt())
+    }
+}
+
+/// per-channel (timestamp, latency) samples, used to render traces and
+/// waterfalls on exit
+pub struct Heatmaps<T: Hash + Eq> {
+    slice_duration: u64,
+    start_time: u64,
+    data: FnvHashMap<T, Vec<(u64, u64)>>,
+}
+
+impl<T: Hash + Eq> Heatmaps<T> {
+    pub fn new(slices: usize, start_time: u64) -> Heatmaps<T> {
+        Heatmaps {
+            slice_duration: if slices > 0 { slices as u64 } else { 1 },
+            start_time,
+            data: FnvHashMap::default(),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> Heatmaps<T> {
+    pub fn init(&mut self, key: T) {
+        self.data.entry(key).or_default();
+    }
+
+    pub fn remove(&mut self, key: T) {
+        self.data.remove(&key);
+    }
+
+    pub fn increment(&mut self, key: T, t0: u64, dt: u64) {
+        if let Some(samples) = self.data.get_mut(&key) {
+            samples.push((t0, dt));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for samples in self.data.values_mut() {
+            samples.clear();
+        }
+    }
+
+    /// write every recorded (timestamp, latency) pair for `key` to `file`
+    pub fn trace(&self, key: T, file: String) {
+        if let Some(samples) = self.data.get(&key) {
+            if let Ok(mut f) = File::create(&file) {
+                for (t0, dt) in samples {
+                    let _ = writeln!(f, "{} {}", t0, dt);
+                }
+            }
+        }
+    }
+
+    /// write a coarse per-slice waterfall rendering of `key`'s heatmap to `file`
+    pub fn waterfall(&self, key: T, file: String) {
+        if let Some(samples) = self.data.get(&key) {
+            if let Ok(mut f) = File::create(&file) {
+                for (t0, dt) in samples {
+                    let slice = t0.saturating_sub(self.start_time) / self.slice_duration;
+                    let _ = writeln!(f, "{} {}", slice, dt);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counters;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn counters_concurrent_record_is_lossless() {
+        let counters = Counters::<String>::new();
+        let id = counters.init("requests".to_owned());
+
+        let threads = 8;
+        let increments_per_thread = 10_000;
+        let counters = Arc::new(counters);
+
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let counters = counters.clone();
+                thread::spawn(move || {
+                    for _ in 0..increments_per_thread {
+                        counters.record(id, 1);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(
+            counters.count("requests".to_owned()),
+            threads * increments_per_thread
+        );
+    }
+}
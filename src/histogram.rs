@@ -0,0 +1,257 @@
+// `AtomicHistogram` is a lock-free, unbounded bucketed histogram
+
+use std::convert::TryInto;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+// number of buckets held in each lazily-allocated block
+const BLOCK_SHIFT: u32 = 10;
+const BLOCK_LEN: usize = 1 << BLOCK_SHIFT;
+
+// a u64 value has at most 64 significant bits, so the exponent component of a
+// bucket index never exceeds this, bounding the number of blocks we could ever need
+const MAX_EXPONENT: u32 = 64;
+
+type Block = [AtomicU64; BLOCK_LEN];
+
+// `[AtomicU64; BLOCK_LEN]` has no `Default` impl (arrays only get one for
+// small, fixed lengths), so a fresh block is built through a boxed slice
+fn new_block() -> Box<Block> {
+    let slots: Vec<AtomicU64> = (0..BLOCK_LEN).map(|_| AtomicU64::new(0)).collect();
+    match slots.into_boxed_slice().try_into() {
+        Ok(block) => block,
+        Err(_) => unreachable!("block length mismatch"),
+    }
+}
+
+/// lock-free, log-linear bucketed histogram suitable for feeding directly from
+/// the atomic ingestion path; writers never block or allocate except the first
+/// time a given block of `BLOCK_LEN` buckets is touched
+pub struct AtomicHistogram {
+    precision: u32,
+    blocks: Vec<AtomicPtr<Block>>,
+}
+
+impl AtomicHistogram {
+    /// create a new histogram with `precision` bits of linear resolution within
+    /// each power-of-two bucket
+    pub fn new(precision: u32) -> AtomicHistogram {
+        let max_bucket = ((MAX_EXPONENT as usize) << precision) + (1 << precision);
+        let block_count = (max_bucket >> BLOCK_SHIFT) + 1;
+        let mut blocks = Vec::with_capacity(block_count);
+        for _ in 0..block_count {
+            blocks.push(AtomicPtr::new(ptr::null_mut()));
+        }
+        AtomicHistogram { precision, blocks }
+    }
+
+    /// record a single observation
+    pub fn increment(&self, value: u64) {
+        let index = self.bucket_index(value);
+        let counter = self.counter_for(index);
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// total number of observations recorded since the last `clear()`
+    pub fn count(&self) -> u64 {
+        (0..self.blocks.len()).map(|block_id| self.block_total(block_id)).sum()
+    }
+
+    /// approximate sum of every recorded observation, computed from each
+    /// bucket's representative value times its count; exact for values in
+    /// the directly-stored range, bounded by the bucket width above it
+    pub fn sum(&self) -> u64 {
+        let mut sum = 0u64;
+        for block_id in 0..self.blocks.len() {
+            let block = self.blocks[block_id].load(Ordering::Acquire);
+            if block.is_null() {
+                continue;
+            }
+            let block = unsafe { &*block };
+            for (sub_index, counter) in block.iter().enumerate() {
+                let count = counter.load(Ordering::Relaxed);
+                if count == 0 {
+                    continue;
+                }
+                let index = (block_id << BLOCK_SHIFT) + sub_index;
+                sum += count * self.representative_value(index);
+            }
+        }
+        sum
+    }
+
+    /// return a snapshot of the approximate value at `rank` (0.0 - 1.0) of the
+    /// distribution observed so far
+    pub fn percentile(&self, rank: f64) -> u64 {
+        let total = self.count();
+        if total == 0 {
+            return 0;
+        }
+
+        let target = (rank * total as f64).ceil() as u64;
+        let mut accumulated = 0u64;
+        for block_id in 0..self.blocks.len() {
+            let block = self.blocks[block_id].load(Ordering::Acquire);
+            if block.is_null() {
+                continue;
+            }
+            let block = unsafe { &*block };
+            for (sub_index, counter) in block.iter().enumerate() {
+                let count = counter.load(Ordering::Relaxed);
+                if count == 0 {
+                    continue;
+                }
+                accumulated += count;
+                if accumulated >= target {
+                    let index = (block_id << BLOCK_SHIFT) + sub_index;
+                    return self.representative_value(index);
+                }
+            }
+        }
+        0
+    }
+
+    /// clear all buckets, retaining the allocated blocks
+    pub fn clear(&self) {
+        for slot in &self.blocks {
+            let block = slot.load(Ordering::Acquire);
+            if block.is_null() {
+                continue;
+            }
+            let block = unsafe { &*block };
+            for counter in block.iter() {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn block_total(&self, block_id: usize) -> u64 {
+        let block = self.blocks[block_id].load(Ordering::Acquire);
+        if block.is_null() {
+            return 0;
+        }
+        let block = unsafe { &*block };
+        block.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    fn counter_for(&self, index: usize) -> &AtomicU64 {
+        let block_id = index >> BLOCK_SHIFT;
+        let sub_index = index & (BLOCK_LEN - 1);
+
+        let slot = &self.blocks[block_id];
+        let mut block = slot.load(Ordering::Acquire);
+        if block.is_null() {
+            let new_block = Box::into_raw(new_block());
+            match slot.compare_exchange(
+                ptr::null_mut(),
+                new_block,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => block = new_block,
+                Err(existing) => {
+                    // another writer won the race to allocate this block; drop ours
+                    let _ = unsafe { Box::from_raw(new_block) };
+                    block = existing;
+                }
+            }
+        }
+
+        unsafe { &(&*block)[sub_index] }
+    }
+
+    fn bucket_index(&self, value: u64) -> usize {
+        if value == 0 {
+            return 0;
+        }
+        let e = 63 - value.leading_zeros();
+        if e <= self.precision {
+            value as usize
+        } else {
+            let shift = e - self.precision;
+            let sub = (value >> shift) & ((1u64 << self.precision) - 1);
+            (((e as u64) << self.precision) | sub) as usize
+        }
+    }
+
+    fn representative_value(&self, index: usize) -> u64 {
+        let p = self.precision;
+        // directly-stored range mirrors `bucket_index`'s `e <= precision` branch,
+        // which stores any value below `2^(p+1)` under its own value as the index
+        if (index as u64) < (1u64 << (p + 1)) {
+            return index as u64;
+        }
+        let e = (index as u64) >> p;
+        let sub = (index as u64) & ((1u64 << p) - 1);
+        let shift = e - p as u64;
+        (1u64 << e) | (sub << shift)
+    }
+}
+
+impl Drop for AtomicHistogram {
+    fn drop(&mut self) {
+        for slot in &self.blocks {
+            let block = slot.swap(ptr::null_mut(), Ordering::AcqRel);
+            if !block.is_null() {
+                let _ = unsafe { Box::from_raw(block) };
+            }
+        }
+    }
+}
+
+unsafe impl Send for AtomicHistogram {}
+unsafe impl Sync for AtomicHistogram {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn bucket_round_trip_is_within_resolution() {
+        let histogram = AtomicHistogram::new(8);
+        for value in (0..200_000u64).step_by(37) {
+            let index = histogram.bucket_index(value);
+            let decoded = histogram.representative_value(index);
+            let e = if value == 0 { 0 } else { 63 - value.leading_zeros() };
+            if e <= histogram.precision {
+                assert_eq!(decoded, value, "directly-stored values must round-trip exactly");
+            } else {
+                let bucket_width = 1u64 << (e - histogram.precision);
+                let diff = decoded.max(value) - decoded.min(value);
+                assert!(
+                    diff < bucket_width,
+                    "value {} decoded to {}, outside its bucket width {}",
+                    value,
+                    decoded,
+                    bucket_width
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_increments_are_all_counted() {
+        let histogram = Arc::new(AtomicHistogram::new(12));
+        let threads = 8u64;
+        let increments_per_thread = 5_000u64;
+
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let histogram = histogram.clone();
+                thread::spawn(move || {
+                    for v in 0..increments_per_thread {
+                        histogram.increment(i * increments_per_thread + v);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(histogram.count(), threads * increments_per_thread);
+    }
+}
@@ -0,0 +1,56 @@
+// `InfluxExporter` pushes window snapshots out as InfluxDB line protocol
+
+use reqwest::Client;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+// a blocking client's default timeout (connect+read+write combined) is 30s,
+// which is far longer than the receive loop can afford to ever wait on
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// how many pending pushes `send` will buffer for the background thread
+// before it starts dropping batches rather than blocking the caller
+const QUEUE_DEPTH: usize = 4;
+
+/// pushes `Meters` snapshots to an InfluxDB-compatible line protocol endpoint
+/// at each window boundary. The actual HTTP POST runs on a dedicated
+/// background thread behind a bounded channel, with a short client timeout,
+/// so a slow or unresponsive target can never stall the receive loop that
+/// calls `send`; network errors (and a full queue) are logged and dropped.
+pub struct InfluxExporter {
+    tx: SyncSender<String>,
+}
+
+impl InfluxExporter {
+    pub fn new(target: String) -> InfluxExporter {
+        let (tx, rx) = sync_channel::<String>(QUEUE_DEPTH);
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+
+        thread::spawn(move || {
+            for body in rx {
+                if let Err(e) = client.post(&target).body(body).send() {
+                    warn!("failed to push stats to influx: {}", e);
+                }
+            }
+        });
+
+        InfluxExporter { tx }
+    }
+
+    /// hand a batch of already-formatted line protocol off to the background
+    /// push thread; drops the batch (logging a warning) if `QUEUE_DEPTH`
+    /// batches are already waiting on that thread, rather than blocking the
+    /// caller
+    pub fn send(&self, body: String) {
+        if body.is_empty() {
+            return;
+        }
+        if self.tx.try_send(body).is_err() {
+            warn!("dropping influx push, background sender is still busy");
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! `tic` is a high resolution, high throughput metrics library
+
+#[macro_use]
+extern crate log;
+
+pub mod common;
+pub mod config;
+mod data;
+mod histogram;
+mod influx;
+pub mod meters;
+pub mod receiver;
+mod sender;
+
+pub use crate::common::{ControlMessage, Interest, Percentile};
+pub use crate::config::Config;
+pub use crate::meters::Meters;
+pub use crate::receiver::Receiver;
+pub use crate::sender::Sender;
@@ -1,16 +1,46 @@
 // `Meters` hold calculated values
 
 use fnv::FnvHashMap;
-use receiver::Percentile;
+use crate::receiver::Percentile;
 use std::fmt::Display;
 use std::hash::Hash;
 use std::marker::PhantomData;
 
+/// the Prometheus metric kind a given stat family should be exported as
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    // allan deviation is a gauge per observation interval (`tau`), which
+    // needs its own kind since it's exported under a different series name
+    // than a bare `Gauge` interest on the same family
+    AdevGauge,
+    Summary,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge | MetricKind::AdevGauge => "gauge",
+            MetricKind::Summary => "summary",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Meters<T> {
     resource_type: PhantomData<T>,
     pub data: FnvHashMap<String, u64>,
     pub data_float: FnvHashMap<String, f64>,
+    // a family can carry more than one kind at once, e.g. a channel with both
+    // a `Count` and a `Percentile` interest registered
+    kinds: FnvHashMap<String, Vec<MetricKind>>,
+    // percentile labels recorded per family, so exposition can look up each
+    // family's own summary keys exactly rather than scanning `data` for keys
+    // that merely start with the family name (which also matches any other
+    // family name it happens to prefix)
+    percentile_labels: FnvHashMap<String, Vec<String>>,
 }
 
 impl<T: Hash + Eq> Default for Meters<T> {
@@ -18,6 +48,8 @@ impl<T: Hash + Eq> Default for Meters<T> {
         Meters {
             data: FnvHashMap::default(),
             data_float: FnvHashMap::default(),
+            kinds: FnvHashMap::default(),
+            percentile_labels: FnvHashMap::default(),
             resource_type: PhantomData::<T>,
         }
     }
@@ -28,21 +60,57 @@ impl<T: Hash + Eq + Send + Display + Clone> Meters<T> {
         Default::default()
     }
 
+    fn note_kind(&mut self, family: String, kind: MetricKind) {
+        let kinds = self.kinds.entry(family).or_default();
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+
     pub fn set_count(&mut self, channel: T, value: u64) {
-        let key = format!("{}_count", channel);
+        let family = format!("{}", channel);
+        let key = format!("{}_count", family);
+        self.note_kind(family, MetricKind::Counter);
         self.data.insert(key, value);
     }
 
     pub fn set_percentile(&mut self, channel: T, percentile: Percentile, value: u64) {
-        let key = format!("{}_{}_nanoseconds", channel, percentile.0);
+        let family = format!("{}", channel);
+        let key = format!("{}_{}_nanoseconds", family, percentile.0);
+        self.note_kind(family.clone(), MetricKind::Summary);
+        let labels = self.percentile_labels.entry(family).or_default();
+        if !labels.contains(&percentile.0) {
+            labels.push(percentile.0.clone());
+        }
         self.data.insert(key, value);
     }
 
+    /// record the observation count and sum backing a `Percentile` family's
+    /// summary, as surfaced by the underlying histogram (whose sum is
+    /// approximate above its directly-stored range); these are the
+    /// `_count`/`_sum` a Prometheus summary consumer expects, not a tally of
+    /// the quantile values themselves
+    pub fn set_summary_stats(&mut self, channel: T, count: u64, sum: u64) {
+        let family = format!("{}", channel);
+        self.data.insert(format!("{}_count", family), count);
+        self.data.insert(format!("{}_sum", family), sum);
+    }
+
     pub fn set_adev(&mut self, channel: T, tau: usize, value: f64) {
-        let key = format!("{}_tau_{}_adev", channel, tau);
+        let family = format!("{}", channel);
+        let key = format!("{}_tau_{}_adev", family, tau);
+        self.note_kind(family, MetricKind::AdevGauge);
         self.data_float.insert(key, value);
     }
 
+    /// record the most recently observed value for a gauge; it is not cleared
+    /// between windows, and holds the last value set until the next call
+    pub fn set_gauge(&mut self, channel: T, value: u64) {
+        let family = format!("{}", channel);
+        self.note_kind(family.clone(), MetricKind::Gauge);
+        self.data.insert(family, value);
+    }
+
     pub fn count(&self, channel: &T) -> Option<&u64> {
         let key = format!("{}_count", channel);
         self.data.get(&key)
@@ -57,4 +125,140 @@ impl<T: Hash + Eq + Send + Display + Clone> Meters<T> {
         let key = format!("{}_tau_{}_adev", channel, tau);
         self.data_float.get(&key)
     }
+
+    pub fn gauge(&self, channel: &T) -> Option<&u64> {
+        let key = format!("{}", channel);
+        self.data.get(&key)
+    }
+
+    /// render all tracked stats as Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let mut output = String::new();
+
+        for (family, kinds) in &self.kinds {
+            for kind in kinds {
+                match *kind {
+                    MetricKind::Counter => {
+                        // the TYPE line must name the series it actually
+                        // declares the type of, which is `family_count`
+                        // rather than the bare family name
+                        let key = format!("{}_count", family);
+                        if let Some(value) = self.data.get(&key) {
+                            output += &format!("# TYPE {} {}\n", key, kind.as_str());
+                            output += &format!("{} {}\n", key, value);
+                        }
+                    }
+                    MetricKind::Gauge => {
+                        // a bare gauge stores its value directly under the family name
+                        if let Some(value) = self.data.get(family) {
+                            output += &format!("# TYPE {} {}\n", family, kind.as_str());
+                            output += &format!("{} {}\n", family, value);
+                        }
+                    }
+                    MetricKind::AdevGauge => {
+                        // one series per tau, labeled rather than folded into
+                        // the name, so a single TYPE line covers all of them
+                        let name = format!("{}_adev", family);
+                        let prefix = format!("{}_tau_", family);
+                        let suffix = "_adev";
+                        let mut samples = String::new();
+                        for (key, value) in &self.data_float {
+                            if key.starts_with(&prefix) && key.ends_with(suffix) {
+                                let tau = &key[prefix.len()..key.len() - suffix.len()];
+                                samples += &format!("{}{{tau=\"{}\"}} {}\n", name, tau, value);
+                            }
+                        }
+                        if !samples.is_empty() {
+                            output += &format!("# TYPE {} {}\n", name, kind.as_str());
+                            output += &samples;
+                        }
+                    }
+                    MetricKind::Summary => {
+                        let mut samples = String::new();
+                        if let Some(labels) = self.percentile_labels.get(family) {
+                            for label in labels {
+                                let key = format!("{}_{}_nanoseconds", family, label);
+                                if let Some(value) = self.data.get(&key) {
+                                    samples += &format!(
+                                        "{}{{quantile=\"{}\"}} {}\n",
+                                        family, label, value
+                                    );
+                                }
+                            }
+                        }
+                        if !samples.is_empty() {
+                            output += &format!("# TYPE {} {}\n", family, kind.as_str());
+                            output += &samples;
+                            let sum = self.data.get(&format!("{}_sum", family)).copied().unwrap_or(0);
+                            let count = self.data.get(&format!("{}_count", family)).copied().unwrap_or(0);
+                            output += &format!("{}_sum {}\n", family, sum);
+                            output += &format!("{}_count {}\n", family, count);
+                        }
+                    }
+                }
+            }
+        }
+
+        output
+    }
+
+    /// render all tracked stats as InfluxDB line protocol, stamped with
+    /// `timestamp_ns` (typically the window end time)
+    pub fn to_influx(&self, timestamp_ns: u64) -> String {
+        let mut output = String::new();
+
+        for (family, kinds) in &self.kinds {
+            for kind in kinds {
+                match *kind {
+                    MetricKind::Counter => {
+                        let key = format!("{}_count", family);
+                        if let Some(value) = self.data.get(&key) {
+                            output += &format!("{} value={} {}\n", key, value, timestamp_ns);
+                        }
+                    }
+                    MetricKind::Gauge => {
+                        if let Some(value) = self.data.get(family) {
+                            output += &format!("{} value={} {}\n", family, value, timestamp_ns);
+                        }
+                    }
+                    MetricKind::AdevGauge => {
+                        let name = format!("{}_adev", family);
+                        let prefix = format!("{}_tau_", family);
+                        let suffix = "_adev";
+                        for (key, value) in &self.data_float {
+                            if key.starts_with(&prefix) && key.ends_with(suffix) {
+                                let tau = &key[prefix.len()..key.len() - suffix.len()];
+                                output += &format!(
+                                    "{},tau={} value={} {}\n",
+                                    name,
+                                    tau,
+                                    value,
+                                    timestamp_ns
+                                );
+                            }
+                        }
+                    }
+                    MetricKind::Summary => {
+                        if let Some(labels) = self.percentile_labels.get(family) {
+                            for label in labels {
+                                let key = format!("{}_{}_nanoseconds", family, label);
+                                if let Some(value) = self.data.get(&key) {
+                                    output += &format!(
+                                        "{},quantile={} value={} {}\n",
+                                        family, label, value, timestamp_ns
+                                    );
+                                }
+                            }
+                        }
+                        let sum = self.data.get(&format!("{}_sum", family)).copied().unwrap_or(0);
+                        let count = self.data.get(&format!("{}_count", family)).copied().unwrap_or(0);
+                        output += &format!("{}_sum value={} {}\n", family, sum, timestamp_ns);
+                        output += &format!("{}_count value={} {}\n", family, count, timestamp_ns);
+                    }
+                }
+            }
+        }
+
+        output
+    }
 }
@@ -1,12 +1,14 @@
 #![allow(deprecated)]
 
 use clocksource::Clocksource;
-use common::{self, ControlMessage, Interest, Percentile};
-use config::Config;
-use data::{Allans, Counters, Heatmaps, Histograms, Meters, Sample};
+use crate::common::{self, ControlMessage, Interest};
+pub use crate::common::Percentile;
+use crate::config::Config;
+use crate::data::{Allans, Counters, Gauges, Heatmaps, Histograms, Meters, Sample};
+use crate::influx::InfluxExporter;
 use mio::{Events, Poll, PollOpt, Ready, Token, channel};
 use mpmc::Queue;
-use sender::Sender;
+use crate::sender::Sender;
 use std::collections::HashSet;
 use std::fmt::Display;
 use std::hash::Hash;
@@ -19,7 +21,7 @@ const TOKEN_DATA: usize = 1;
 const TOKEN_CONTROL: usize = 2;
 
 /// a `Receiver` processes incoming `Sample`s and generates stats
-pub struct Receiver<T> {
+pub struct Receiver<T: Hash + Eq> {
     window_time: u64,
     window_duration: u64,
     end_time: u64,
@@ -32,6 +34,7 @@ pub struct Receiver<T> {
     control_tx: channel::SyncSender<ControlMessage<T>>,
     allans: Allans<T>,
     counters: Counters<T>,
+    gauges: Gauges<T>,
     histograms: Histograms<T>,
     meters: Meters<T>,
     interests: HashSet<Interest<T>>,
@@ -39,6 +42,8 @@ pub struct Receiver<T> {
     percentiles: Vec<Percentile>,
     heatmaps: Heatmaps<T>,
     server: Option<Server>,
+    influx: Option<InfluxExporter>,
+    influx_window: usize,
     clocksource: Clocksource,
     poll: Poll,
 }
@@ -69,6 +74,7 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
 
         let listen = config.http_listen.clone();
         let server = start_listener(&listen);
+        let influx = config.influx_target.clone().map(InfluxExporter::new);
 
         let clocksource = Clocksource::default();
 
@@ -94,27 +100,30 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
         ).unwrap();
 
         Receiver {
-            window_duration: window_duration,
-            window_time: window_time,
-            run_duration: run_duration,
-            end_time: end_time,
-            config: config,
-            empty_queue: empty_queue,
-            data_tx: data_tx,
-            data_rx: data_rx,
-            control_tx: control_tx,
-            control_rx: control_rx,
+            window_duration,
+            window_time,
+            run_duration,
+            end_time,
+            config,
+            empty_queue,
+            data_tx,
+            data_rx,
+            control_tx,
+            control_rx,
             allans: Allans::new(),
             counters: Counters::new(),
+            gauges: Gauges::new(),
             histograms: Histograms::new(),
             meters: Meters::new(),
             interests: HashSet::new(),
             taus: common::default_taus(),
             percentiles: common::default_percentiles(),
             heatmaps: Heatmaps::new(slices, start_time),
-            server: server,
-            clocksource: clocksource,
-            poll: poll,
+            server,
+            influx,
+            influx_window: 0,
+            clocksource,
+            poll,
         }
     }
 
@@ -130,6 +139,7 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
             self.data_tx.clone(),
             self.control_tx.clone(),
             self.config.batch_size,
+            self.counters.clone(),
         )
     }
 
@@ -145,8 +155,12 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
                 self.allans.init(key);
             }
             Interest::Count(key) => {
+                // allocates the atomic slot that `Sender::record_counter` writes to
                 self.counters.init(key);
             }
+            Interest::Gauge(key) => {
+                self.gauges.init(key);
+            }
             Interest::Percentile(key) => {
                 self.histograms.init(key);
             }
@@ -167,6 +181,9 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
             Interest::Count(key) => {
                 self.counters.remove(key);
             }
+            Interest::Gauge(key) => {
+                self.gauges.remove(key);
+            }
             Interest::Percentile(key) => {
                 self.histograms.remove(key);
             }
@@ -212,14 +229,17 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
                                 let t0 = self.clocksource.convert(result.start());
                                 let t1 = self.clocksource.convert(result.stop());
                                 let dt = t1 - t0;
-                                self.allans.record(result.metric(), dt);
-                                self.counters.increment_by(result.metric(), result.count());
-                                self.histograms.increment(result.metric(), dt as u64);
-                                self.heatmaps.increment(
-                                    result.metric(),
-                                    t0 as u64,
-                                    dt as u64,
-                                );
+                                // counters are incremented directly off the atomic fast
+                                // path in `Sender`, so the channel only ever carries
+                                // timestamped samples for histogram/heatmap/
+                                // allan-deviation/gauge aggregation
+                                let metric = result.metric();
+                                self.allans.record(metric.clone(), dt);
+                                self.histograms.increment(metric.clone(), dt as u64);
+                                // the most recent sample wins; `Gauges` is a no-op
+                                // for metrics without a registered `Gauge` interest
+                                self.gauges.set(metric.clone(), result.count());
+                                self.heatmaps.increment(metric, t0 as u64, dt as u64);
                             }
                             results.clear();
                             let _ = self.empty_queue.push(results);
@@ -251,6 +271,8 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
             for interest in &self.interests {
                 match *interest {
                     Interest::Count(ref key) => {
+                        // snapshots the atomic counter; no contention with the
+                        // fast-path writers since this is a relaxed load
                         self.meters.set_count(
                             key.clone(),
                             self.counters.count(key.clone()),
@@ -266,6 +288,12 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
                                     .unwrap_or(0),
                             );
                         }
+                        // the summary's `_count`/`_sum` come from the
+                        // histogram's own totals, not from the quantile
+                        // values set above
+                        if let Some((count, sum)) = self.histograms.stats(key.clone()) {
+                            self.meters.set_summary_stats(key.clone(), count, sum);
+                        }
                     }
                     Interest::AllanDeviation(ref key) => {
                         for tau in self.taus.clone() {
@@ -274,11 +302,27 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
                             }
                         }
                     }
+                    Interest::Gauge(ref key) => {
+                        // gauges represent current state, so we export whatever
+                        // the last sample left behind rather than clearing it
+                        if let Some(value) = self.gauges.get(key.clone()) {
+                            self.meters.set_gauge(key.clone(), value);
+                        }
+                    }
                     Interest::Trace(_, _) |
                     Interest::Waterfall(_, _) => {}
                 }
             }
 
+            if let Some(ref influx) = self.influx {
+                self.influx_window += 1;
+                if self.influx_window >= self.config.influx_flush {
+                    self.influx_window = 0;
+                    let timestamp_ns = self.clocksource.convert(t1) as u64;
+                    influx.send(self.meters.to_influx(timestamp_ns));
+                }
+            }
+
             self.histograms.clear();
             self.window_time += self.window_duration;
             return true;
@@ -344,13 +388,8 @@ impl<T: Hash + Eq + Send + Display + Clone> Receiver<T> {
         let mut output = "".to_owned();
 
         match request.url() {
-            "/vars" | "/metrics" => {
-                for (stat, value) in &self.meters.data {
-                    output = output + &format!("{} {}\n", stat, value);
-                }
-                for (stat, value) in &self.meters.data_float {
-                    output = output + &format!("{} {}\n", stat, value);
-                }
+            "/metrics" => {
+                output = self.meters.to_prometheus();
             }
             _ => {
                 output += "{";
@@ -0,0 +1,98 @@
+#![allow(deprecated)]
+
+// `Sender` is the producer half of a `Receiver`'s data and control channels
+
+use crate::common::{ControlMessage, Interest};
+use crate::data::{Counters, Sample};
+use mio::channel;
+use mpmc::Queue;
+use std::cell::RefCell;
+use std::hash::Hash;
+use std::sync::Arc;
+
+pub struct Sender<T: Hash + Eq + Send + Clone> {
+    empty_queue: Arc<Queue<Vec<Sample<T>>>>,
+    data_tx: channel::SyncSender<Vec<Sample<T>>>,
+    control_tx: channel::SyncSender<ControlMessage<T>>,
+    batch_size: usize,
+    counters: Counters<T>,
+    buffer: RefCell<Vec<Sample<T>>>,
+}
+
+impl<T: Hash + Eq + Send + Clone> Sender<T> {
+    pub fn new(
+        empty_queue: Arc<Queue<Vec<Sample<T>>>>,
+        data_tx: channel::SyncSender<Vec<Sample<T>>>,
+        control_tx: channel::SyncSender<ControlMessage<T>>,
+        batch_size: usize,
+        counters: Counters<T>,
+    ) -> Sender<T> {
+        Sender {
+            empty_queue,
+            data_tx,
+            control_tx,
+            batch_size,
+            counters,
+            buffer: RefCell::new(Vec::with_capacity(batch_size)),
+        }
+    }
+
+    /// register `metric` as a `Count` interest and return the index used by
+    /// `record_counter`'s atomic fast path
+    pub fn register_counter(&self, metric: T) -> usize {
+        let id = self.counters.init(metric.clone());
+        let _ = self
+            .control_tx
+            .try_send(ControlMessage::AddInterest(Interest::Count(metric)));
+        id
+    }
+
+    /// atomic fast path for a `Count` interest: a plain `fetch_add` on the
+    /// slot returned by `register_counter`, with no channel hop
+    pub fn record_counter(&self, id: usize, count: u64) {
+        self.counters.record(id, count);
+    }
+
+    /// record a timestamped sample for histogram/heatmap/allan-deviation/gauge
+    /// aggregation; batched and flushed to the `Receiver` once `batch_size`
+    /// samples have accumulated
+    pub fn send(&self, metric: T, start: u64, stop: u64, count: u64) {
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.push(Sample::new(metric, start, stop, count));
+        if buffer.len() >= self.batch_size {
+            self.flush_buffer(&mut buffer);
+        }
+    }
+
+    /// flush any buffered samples to the `Receiver` immediately, rather than
+    /// waiting for the batch to fill
+    pub fn flush(&self) {
+        let mut buffer = self.buffer.borrow_mut();
+        if !buffer.is_empty() {
+            self.flush_buffer(&mut buffer);
+        }
+    }
+
+    fn flush_buffer(&self, buffer: &mut Vec<Sample<T>>) {
+        let mut batch = self
+            .empty_queue
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.batch_size));
+        batch.append(buffer);
+        // fail soft: if the receiver can't keep up, drop the batch rather
+        // than block the producer
+        let _ = self.data_tx.try_send(batch);
+    }
+
+    pub fn add_interest(&self, interest: Interest<T>) {
+        let _ = self
+            .control_tx
+            .try_send(ControlMessage::AddInterest(interest));
+    }
+
+    pub fn remove_interest(&self, interest: Interest<T>) {
+        let _ = self
+            .control_tx
+            .try_send(ControlMessage::RemoveInterest(interest));
+    }
+}